@@ -1,16 +1,40 @@
+use pyo3::exceptions::{PyKeyError, PyRuntimeError};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use pyo3::PyIterProtocol;
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::mem::ManuallyDrop;
-use std::ops::DerefMut;
-use yrs::types::map::{MapEvent, MapIter};
+use std::rc::Rc;
+use yrs::types::map::MapEvent;
 use yrs::{Map, Transaction};
 
 use crate::shared_types::SharedType;
 use crate::type_conversions::{PyValueWrapper, ToPython};
 use crate::y_transaction::YTransaction;
 
+/// Like `ToPython`, but reconstructs nested shared types (`YMap`, `YArray`, `YText`) as their
+/// proper Python wrapper bound to the same document, rather than flattening them through
+/// `into_py` and losing the ability to mutate them in place.
+///
+/// This belongs alongside `ToPython` in `type_conversions.rs` so every shared type's getters
+/// benefit from it, not just `YMap`'s. It is defined here for now because that module isn't part
+/// of this change; once it is, `YMap::__getitem__` and `YMap::entries` below should switch to calling `ToPython`
+/// directly and this trait/impl should be deleted.
+trait IntoSharedPy {
+    fn into_shared_py(self, py: Python) -> PyObject;
+}
+
+impl IntoSharedPy for yrs::types::Value {
+    fn into_shared_py(self, py: Python) -> PyObject {
+        match self {
+            yrs::types::Value::YMap(map) => YMap::from(map).into_py(py),
+            yrs::types::Value::YArray(array) => crate::y_array::YArray::from(array).into_py(py),
+            yrs::types::Value::YText(text) => crate::y_text::YText::from(text).into_py(py),
+            other => other.into_py(py),
+        }
+    }
+}
+
 /// Collection used to store key-value entries in an unordered manner. Keys are always represented
 /// as UTF-8 strings. Values can be any value type supported by Yrs: JSON-like primitives as well as
 /// shared data types.
@@ -20,11 +44,81 @@ use crate::y_transaction::YTransaction;
 /// by different peers are resolved into a single value using document id seniority to establish
 /// order.
 #[pyclass(unsendable)]
-pub struct YMap(pub SharedType<Map, HashMap<String, PyObject>>);
+pub struct YMap {
+    start: SharedType<Map, HashMap<String, PyObject>>,
+    state: Rc<PySharedState>,
+}
 
 impl From<Map> for YMap {
     fn from(v: Map) -> Self {
-        YMap(SharedType::new(v))
+        YMap {
+            start: SharedType::new(v),
+            state: Rc::new(PySharedState::new()),
+        }
+    }
+}
+
+/// Tracks how many `YMapIterator`s are currently leaked out of a `YMap` and bumps a generation
+/// counter every time the map is mutated, so that a leaked iterator can detect staleness on its
+/// next `__next__` call instead of dereferencing memory that may no longer describe the same
+/// collection. This plays the role the `PySharedState`/`PyLeaked` split plays for other shared
+/// types: it lets us hand a borrow out across the FFI boundary without keeping a live Rust borrow
+/// checker guard alive for the whole lifetime of the Python-side object.
+///
+/// Known limitation: this state is owned by the `YMap` wrapper (`Rc<PySharedState>`), not by the
+/// underlying yrs branch. `YMap::from` constructs a fresh one every time, so two independent
+/// Python-level handles to the *same* map (e.g. two `doc.get_map(name)` calls) get independent
+/// generation counters and independent `mutably_borrowed` flags — mutating through one handle is
+/// not observed by an iterator or `borrow_mut` on the other. Closing this gap for real requires
+/// keying the state off the branch identity in `shared_types::SharedType`/`Map` itself, which is
+/// outside this file; until then, only iterators and mutations that go through the *same* `YMap`
+/// object are protected.
+struct PySharedState {
+    leak_count: Cell<usize>,
+    mutably_borrowed: Cell<bool>,
+    generation: Cell<u64>,
+}
+
+impl PySharedState {
+    fn new() -> Self {
+        PySharedState {
+            leak_count: Cell::new(0),
+            mutably_borrowed: Cell::new(false),
+            generation: Cell::new(0),
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    /// Registers a new leaked iterator and returns the generation it was created at.
+    fn leak(&self) -> u64 {
+        self.leak_count.set(self.leak_count.get() + 1);
+        self.generation()
+    }
+
+    /// Releases a previously leaked iterator.
+    fn unleak(&self) {
+        self.leak_count.set(self.leak_count.get() - 1);
+    }
+
+    /// Marks the start of a mutation, bumping the generation so that any iterators leaked out
+    /// before this call observe a mismatch on their next access. Fails if another mutable borrow
+    /// is already outstanding.
+    fn borrow_mut(&self) -> PyResult<()> {
+        if self.mutably_borrowed.get() {
+            return Err(PyRuntimeError::new_err(
+                "YMap is already mutably borrowed",
+            ));
+        }
+        self.mutably_borrowed.set(true);
+        self.generation.set(self.generation.get().wrapping_add(1));
+        Ok(())
+    }
+
+    fn release_mut(&self) {
+        self.mutably_borrowed.set(false);
     }
 }
 
@@ -44,7 +138,10 @@ impl YMap {
             let v: PyObject = v.into();
             map.insert(k, v);
         }
-        Ok(YMap(SharedType::Prelim(map)))
+        Ok(YMap {
+            start: SharedType::Prelim(map),
+            state: Rc::new(PySharedState::new()),
+        })
     }
 
     /// Returns true if this is a preliminary instance of `YMap`.
@@ -54,7 +151,7 @@ impl YMap {
     /// document store and cannot be nested again: attempt to do so will result in an exception.
     #[getter]
     pub fn prelim(&self) -> bool {
-        match &self.0 {
+        match &self.start {
             SharedType::Prelim(_) => true,
             _ => false,
         }
@@ -62,19 +159,30 @@ impl YMap {
 
     /// Returns a number of entries stored within this instance of `YMap`.
     pub fn length(&self, txn: &YTransaction) -> u32 {
-        match &self.0 {
+        match &self.start {
             SharedType::Integrated(v) => v.len(txn),
             SharedType::Prelim(v) => v.len() as u32,
         }
     }
 
-    /// Converts contents of this `YMap` instance into a JSON representation.
+    /// Converts contents of this `YMap` instance into a JSON representation. Nested shared types
+    /// (whether integrated or still preliminary) are recursively materialized, so the result is a
+    /// plain `dict` all the way down rather than stopping at the first shared boundary.
     pub fn to_json(&self, txn: &YTransaction) -> PyResult<PyObject> {
-        Python::with_gil(|py| match &self.0 {
+        Python::with_gil(|py| match &self.start {
             SharedType::Integrated(v) => Ok(v.to_json(txn).into_py(py)),
             SharedType::Prelim(v) => {
                 let dict = PyDict::new(py);
                 for (k, v) in v.iter() {
+                    let v = if let Ok(nested) = v.extract::<PyRef<YMap>>(py) {
+                        nested.to_json(txn)?
+                    } else if let Ok(nested) = v.extract::<PyRef<crate::y_array::YArray>>(py) {
+                        nested.to_json(txn)?
+                    } else if let Ok(nested) = v.extract::<PyRef<crate::y_text::YText>>(py) {
+                        nested.to_json(txn)?
+                    } else {
+                        v.clone()
+                    };
                     dict.set_item(k, v)?;
                 }
                 Ok(dict.into())
@@ -84,8 +192,9 @@ impl YMap {
 
     /// Sets a given `key`-`value` entry within this instance of `YMap`. If another entry was
     /// already stored under given `key`, it will be overridden with new `value`.
-    pub fn set(&mut self, txn: &mut YTransaction, key: &str, value: PyObject) {
-        match &mut self.0 {
+    pub fn set(&mut self, txn: &mut YTransaction, key: &str, value: PyObject) -> PyResult<()> {
+        self.state.borrow_mut()?;
+        match &mut self.start {
             SharedType::Integrated(v) => {
                 v.insert(txn, key.to_string(), PyValueWrapper(value));
             }
@@ -93,11 +202,14 @@ impl YMap {
                 v.insert(key.to_string(), value);
             }
         }
+        self.state.release_mut();
+        Ok(())
     }
 
     /// Removes an entry identified by a given `key` from this instance of `YMap`, if such exists.
-    pub fn delete(&mut self, txn: &mut YTransaction, key: &str) {
-        match &mut self.0 {
+    pub fn delete(&mut self, txn: &mut YTransaction, key: &str) -> PyResult<()> {
+        self.state.borrow_mut()?;
+        match &mut self.start {
             SharedType::Integrated(v) => {
                 v.remove(txn, key);
             }
@@ -105,32 +217,21 @@ impl YMap {
                 v.remove(key);
             }
         }
-    }
-
-    /// Returns value of an entry stored under given `key` within this instance of `YMap`,
-    /// or `undefined` if no such entry existed.
-    pub fn get(&self, txn: &mut YTransaction, key: &str) -> PyObject {
-        match &self.0 {
-            SharedType::Integrated(v) => Python::with_gil(|py| {
-                if let Some(value) = v.get(txn, key) {
-                    value.into_py(py)
-                } else {
-                    py.None()
-                }
-            }),
-            SharedType::Prelim(v) => {
-                if let Some(value) = v.get(key) {
-                    value.clone()
-                } else {
-                    Python::with_gil(|py| py.None())
-                }
-            }
-        }
+        self.state.release_mut();
+        Ok(())
     }
 
     /// Returns an iterator that can be used to traverse over all entries stored within this
     /// instance of `YMap`. Order of entry is not specified.
     ///
+    /// Entries are snapshotted eagerly while `txn` is still known to be valid, so the iterator
+    /// never holds a borrow into the map or the transaction past this call: nothing it does after
+    /// `entries()` returns can dereference memory that transaction commit/drop or a dropped `YMap`
+    /// might have invalidated. The iterator is still borrow-tracked against the map's own
+    /// generation counter, so mutating the map (via `set`/`delete`) before the iterator is fully
+    /// consumed raises a `RuntimeError` on the next advance, instead of silently iterating over
+    /// data that no longer matches reality.
+    ///
     /// Example:
     ///
     /// ```python
@@ -146,34 +247,239 @@ impl YMap {
     ///         print(key, value)
     /// ```
     pub fn entries(&self, txn: &mut YTransaction) -> YMapIterator {
-        match &self.0 {
-            SharedType::Integrated(val) => unsafe {
-                let this: *const Map = val;
-                let tx: *const Transaction = &txn.0 as *const _;
-                let shared_iter =
-                    SharedYMapIterator::Integrated((*this).iter(tx.as_ref().unwrap()));
-                YMapIterator(ManuallyDrop::new(shared_iter))
-            },
-            SharedType::Prelim(val) => unsafe {
-                let this: *const HashMap<String, PyObject> = val;
-                let shared_iter = SharedYMapIterator::Prelim((*this).iter());
-                YMapIterator(ManuallyDrop::new(shared_iter))
-            },
+        let generation = self.state.leak();
+        let entries: Vec<(String, PyObject)> = Python::with_gil(|py| match &self.start {
+            SharedType::Integrated(v) => v
+                .iter(txn)
+                .map(|(k, v)| (k.to_string(), v.into_shared_py(py)))
+                .collect(),
+            SharedType::Prelim(v) => v.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        });
+        YMapIterator {
+            entries: entries.into_iter(),
+            state: self.state.clone(),
+            generation,
         }
     }
+
+    /// Returns value of an entry stored under given `key` within this instance of `YMap`, falling
+    /// back to `default` (`None` unless specified) if no such entry existed. Mirrors `dict.get`.
+    #[args(default = "None")]
+    pub fn get(&self, txn: &mut YTransaction, key: &str, default: Option<PyObject>) -> PyObject {
+        match self.__getitem__(txn, key) {
+            Ok(value) => value,
+            Err(_) => default.unwrap_or_else(|| Python::with_gil(|py| py.None())),
+        }
+    }
+
+    /// Returns an iterator over the keys of this `YMap`. Equivalent to `dict.keys()`.
+    pub fn keys(&self, txn: &mut YTransaction) -> YMapKeyIterator {
+        YMapKeyIterator(self.entries(txn))
+    }
+
+    /// Returns an iterator over the values of this `YMap`. Equivalent to `dict.values()`.
+    pub fn values(&self, txn: &mut YTransaction) -> YMapValueIterator {
+        YMapValueIterator(self.entries(txn))
+    }
+
+    /// Returns an iterator over the key-value pairs of this `YMap`. Equivalent to `dict.items()`.
+    pub fn items(&self, txn: &mut YTransaction) -> YMapIterator {
+        self.entries(txn)
+    }
+
+    /// Removes an entry identified by a given `key`, returning its value. Raises `KeyError` if
+    /// `key` is absent and no `default` was given, mirroring `dict.pop`. Unlike `dict.pop`, a
+    /// `default` of `None` counts as no default, so `pop(txn, key, None)` on an absent `key`
+    /// raises `KeyError` too.
+    #[args(default = "None")]
+    pub fn pop(
+        &mut self,
+        txn: &mut YTransaction,
+        key: &str,
+        default: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        match self.__getitem__(txn, key) {
+            Ok(value) => {
+                self.delete(txn, key)?;
+                Ok(value)
+            }
+            Err(err) => default.ok_or(err),
+        }
+    }
+
+    /// Inserts every key-value pair of `other` into this `YMap`, overwriting existing entries.
+    pub fn update(&mut self, txn: &mut YTransaction, other: &PyDict) -> PyResult<()> {
+        for (key, value) in other.iter() {
+            let key = key.downcast::<pyo3::types::PyString>()?.to_string();
+            self.set(txn, &key, value.into())?;
+        }
+        Ok(())
+    }
+
+    pub fn __getitem__(&self, txn: &mut YTransaction, key: &str) -> PyResult<PyObject> {
+        match &self.start {
+            SharedType::Integrated(v) => Python::with_gil(|py| {
+                v.get(txn, key)
+                    .map(|value| value.into_shared_py(py))
+                    .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+            }),
+            SharedType::Prelim(v) => v
+                .get(key)
+                .map(|value| value.clone())
+                .ok_or_else(|| PyKeyError::new_err(key.to_string())),
+        }
+    }
+
+    pub fn __setitem__(
+        &mut self,
+        txn: &mut YTransaction,
+        key: &str,
+        value: PyObject,
+    ) -> PyResult<()> {
+        self.set(txn, key, value)
+    }
+
+    pub fn __delitem__(&mut self, txn: &mut YTransaction, key: &str) -> PyResult<()> {
+        self.delete(txn, key)
+    }
+
+    pub fn __contains__(&self, txn: &mut YTransaction, key: &str) -> bool {
+        self.__getitem__(txn, key).is_ok()
+    }
+
+    pub fn __len__(&self, txn: &YTransaction) -> u32 {
+        self.length(txn)
+    }
+
+    pub fn __iter__(&self, txn: &mut YTransaction) -> YMapKeyIterator {
+        self.keys(txn)
+    }
+
+    /// Subscribes a `callback` function to be called whenever a transaction commits changes made
+    /// to this instance of `YMap`. The callback is passed a freshly constructed `YMapEvent`.
+    /// Returns a `YSubscription` that keeps the registration alive: drop it, or call
+    /// `unobserve()` on it explicitly, to stop receiving further notifications.
+    pub fn observe(&mut self, callback: PyObject) -> PyResult<YSubscription> {
+        match &mut self.start {
+            SharedType::Integrated(map) => {
+                let sub = map.observe(move |txn, event| {
+                    Python::with_gil(|py| {
+                        let event = match Py::new(py, YMapEvent::new(event, txn)) {
+                            Ok(event) => event,
+                            Err(err) => {
+                                err.restore(py);
+                                return;
+                            }
+                        };
+                        if let Err(err) = callback.call1(py, (event.clone(),)) {
+                            err.restore(py);
+                        }
+                        event.borrow_mut(py).invalidate();
+                    });
+                });
+                Ok(YSubscription::new(sub))
+            }
+            SharedType::Prelim(_) => Err(PyRuntimeError::new_err(
+                "Cannot observe a preliminary YMap. Integrate it into a document first.",
+            )),
+        }
+    }
+
+    /// Subscribes a `callback` function to be called whenever a transaction commits changes made
+    /// to this instance of `YMap` or to any shared type nested within it. The callback is passed
+    /// a list of events, one per changed container (`YMapEvent`, `YArrayEvent` or `YTextEvent`
+    /// depending on what changed); `path()` on each disambiguates which nested container changed.
+    /// Returns a `YSubscription` analogous to the one returned by `observe`.
+    pub fn observe_deep(&mut self, callback: PyObject) -> PyResult<YSubscription> {
+        match &mut self.start {
+            SharedType::Integrated(map) => {
+                let sub = map.observe_deep(move |txn, events| {
+                    Python::with_gil(|py| {
+                        // `YMapEvent`s are invalidated below once the callback returns, since they
+                        // borrow from `txn`/the yrs event for this call only; `YArrayEvent`/
+                        // `YTextEvent` need the equivalent treatment in their own modules.
+                        let mut map_events = Vec::new();
+                        let mut objects = Vec::with_capacity(events.len());
+                        for event in events.iter() {
+                            let object = match event {
+                                yrs::types::Event::Map(event) => {
+                                    match Py::new(py, YMapEvent::new(event, txn)) {
+                                        Ok(event) => {
+                                            map_events.push(event.clone());
+                                            event.into_py(py)
+                                        }
+                                        Err(err) => {
+                                            err.restore(py);
+                                            return;
+                                        }
+                                    }
+                                }
+                                yrs::types::Event::Array(event) => {
+                                    crate::y_array::YArrayEvent::new(event, txn).into_py(py)
+                                }
+                                yrs::types::Event::Text(event) => {
+                                    crate::y_text::YTextEvent::new(event, txn).into_py(py)
+                                }
+                            };
+                            objects.push(object);
+                        }
+                        if let Err(err) = callback.call1(py, (objects,)) {
+                            err.restore(py);
+                        }
+                        for event in map_events {
+                            event.borrow_mut(py).invalidate();
+                        }
+                    });
+                });
+                Ok(YSubscription::new(sub))
+            }
+            SharedType::Prelim(_) => Err(PyRuntimeError::new_err(
+                "Cannot observe a preliminary YMap. Integrate it into a document first.",
+            )),
+        }
+    }
+}
+
+/// Opaque handle returned by `YMap.observe`/`YMap.observe_deep`. Dropping it (or calling
+/// `unobserve()` explicitly) unregisters the underlying callback.
+#[pyclass(unsendable)]
+pub struct YSubscription(Option<Box<dyn std::any::Any>>);
+
+impl YSubscription {
+    fn new<T: 'static>(subscription: T) -> Self {
+        YSubscription(Some(Box::new(subscription)))
+    }
 }
 
-pub enum SharedYMapIterator {
-    Integrated(MapIter<'static>),
-    Prelim(std::collections::hash_map::Iter<'static, String, PyObject>),
+#[pymethods]
+impl YSubscription {
+    /// Stops the associated callback from being called again.
+    pub fn unobserve(&mut self) {
+        self.0.take();
+    }
 }
 
 #[pyclass(unsendable)]
-pub struct YMapIterator(ManuallyDrop<SharedYMapIterator>);
+pub struct YMapIterator {
+    entries: std::vec::IntoIter<(String, PyObject)>,
+    state: Rc<PySharedState>,
+    generation: u64,
+}
+
+impl YMapIterator {
+    fn next_entry(&mut self) -> PyResult<Option<(String, PyObject)>> {
+        if self.generation != self.state.generation() {
+            return Err(PyRuntimeError::new_err(
+                "YMap changed size during iteration",
+            ));
+        }
+        Ok(self.entries.next())
+    }
+}
 
 impl Drop for YMapIterator {
     fn drop(&mut self) {
-        unsafe { ManuallyDrop::drop(&mut self.0) }
+        self.state.unleak();
     }
 }
 
@@ -182,17 +488,47 @@ impl<'p> PyIterProtocol for YMapIterator {
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<(String, PyObject)> {
-        match slf.0.deref_mut() {
-            SharedYMapIterator::Integrated(iter) => {
-                Python::with_gil(|py| iter.next().map(|(k, v)| (k.to_string(), v.into_py(py))))
-            }
-            SharedYMapIterator::Prelim(iter) => iter.next().map(|(k, v)| (k.clone(), v.clone())),
-        }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<(String, PyObject)>> {
+        slf.next_entry()
+    }
+}
+
+/// Iterator over the keys of a `YMap`, as returned by `YMap.keys()`/`YMap.__iter__()`.
+#[pyclass(unsendable)]
+pub struct YMapKeyIterator(YMapIterator);
+
+#[pyproto]
+impl<'p> PyIterProtocol for YMapKeyIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<String>> {
+        Ok(slf.0.next_entry()?.map(|(key, _)| key))
+    }
+}
+
+/// Iterator over the values of a `YMap`, as returned by `YMap.values()`.
+#[pyclass(unsendable)]
+pub struct YMapValueIterator(YMapIterator);
+
+#[pyproto]
+impl<'p> PyIterProtocol for YMapValueIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        Ok(slf.0.next_entry()?.map(|(_, value)| value))
     }
 }
 
 /// Event generated by `YMap.observe` method. Emitted during transaction commit phase.
+///
+/// Only valid for the duration of the observer callback it was passed to: the `MapEvent`/
+/// `Transaction` it points to are borrowed from yrs for that single synchronous call and do not
+/// outlive it. If Python code retains the event past the callback returning, `invalidate()` has
+/// already nulled out the pointers, so further attribute access raises a clean `RuntimeError`
+/// instead of dereferencing memory the commit has since freed. The exception is `target` and
+/// `keys` when they were already read during the callback: those return their cached values.
 #[pyclass(unsendable)]
 pub struct YMapEvent {
     inner: *const MapEvent,
@@ -213,12 +549,27 @@ impl YMapEvent {
         }
     }
 
-    fn inner(&self) -> &MapEvent {
-        unsafe { self.inner.as_ref().unwrap() }
+    /// Nulls out the borrowed pointers so that any access after the observer callback that
+    /// constructed this event has returned fails loudly instead of dereferencing stale memory.
+    fn invalidate(&mut self) {
+        self.inner = std::ptr::null();
+        self.txn = std::ptr::null();
     }
 
-    fn txn(&self) -> &Transaction {
-        unsafe { self.txn.as_ref().unwrap() }
+    fn inner(&self) -> PyResult<&MapEvent> {
+        unsafe { self.inner.as_ref() }.ok_or_else(|| {
+            PyRuntimeError::new_err(
+                "YMapEvent is no longer valid outside of the observer callback it was passed to",
+            )
+        })
+    }
+
+    fn txn(&self) -> PyResult<&Transaction> {
+        unsafe { self.txn.as_ref() }.ok_or_else(|| {
+            PyRuntimeError::new_err(
+                "YMapEvent is no longer valid outside of the observer callback it was passed to",
+            )
+        })
     }
 }
 
@@ -226,21 +577,24 @@ impl YMapEvent {
 impl YMapEvent {
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
-    pub fn target(&mut self) -> PyObject {
+    pub fn target(&mut self) -> PyResult<PyObject> {
         if let Some(target) = self.target.as_ref() {
-            target.clone()
+            Ok(target.clone())
         } else {
+            let inner = self.inner()?;
             let target: PyObject =
-                Python::with_gil(|py| YMap::from(self.inner().target().clone()).into_py(py));
+                Python::with_gil(|py| YMap::from(inner.target().clone()).into_py(py));
             self.target = Some(target.clone());
-            target
+            Ok(target)
         }
     }
 
     /// Returns an array of keys and indexes creating a path from root type down to current instance
     /// of shared type (accessible via `target` getter).
-    pub fn path(&self) -> PyObject {
-        Python::with_gil(|py| self.inner().path(self.txn()).into_py(py))
+    pub fn path(&self) -> PyResult<PyObject> {
+        let inner = self.inner()?;
+        let txn = self.txn()?;
+        Ok(Python::with_gil(|py| inner.path(txn).into_py(py)))
     }
 
     /// Returns a list of key-value changes made over corresponding `YMap` collection within
@@ -248,12 +602,14 @@ impl YMapEvent {
     ///
     /// - { action: 'add'|'update'|'delete', oldValue: any|undefined, newValue: any|undefined }
     #[getter]
-    pub fn keys(&mut self) -> PyObject {
+    pub fn keys(&mut self) -> PyResult<PyObject> {
         if let Some(keys) = &self.keys {
-            keys.clone()
+            Ok(keys.clone())
         } else {
+            let inner = self.inner()?;
+            let txn = self.txn()?;
             let keys: PyObject = Python::with_gil(|py| {
-                let keys = self.inner().keys(self.txn());
+                let keys = inner.keys(txn);
                 let result = PyDict::new(py);
                 for (key, value) in keys.iter() {
                     let key = &**key;
@@ -263,7 +619,199 @@ impl YMapEvent {
             });
 
             self.keys = Some(keys.clone());
-            keys
+            Ok(keys)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyList;
+
+    fn doc_map(name: &str) -> (yrs::Doc, YMap) {
+        let doc = yrs::Doc::new();
+        let map = doc.get_map(name);
+        (doc, YMap::from(map))
+    }
+
+    #[test]
+    fn borrow_mut_rejects_reentrant_mutation() {
+        let state = PySharedState::new();
+        state.borrow_mut().unwrap();
+        assert!(state.borrow_mut().is_err());
+        state.release_mut();
+        assert!(state.borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn generation_bumps_on_every_mutation() {
+        let state = PySharedState::new();
+        let generation = state.leak();
+        assert_eq!(generation, state.generation());
+        state.borrow_mut().unwrap();
+        state.release_mut();
+        assert_ne!(generation, state.generation());
+    }
+
+    #[test]
+    fn iterator_raises_when_map_mutated_during_iteration() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (doc, mut map) = doc_map("map");
+            let mut txn = YTransaction(doc.transact());
+            map.set(&mut txn, "a", py.None()).unwrap();
+            map.set(&mut txn, "b", py.None()).unwrap();
+
+            let mut iter = map.entries(&mut txn);
+            assert!(iter.next_entry().unwrap().is_some());
+
+            map.set(&mut txn, "c", py.None()).unwrap();
+
+            let err = iter.next_entry().unwrap_err();
+            assert!(err.to_string().contains("changed size during iteration"));
+        });
+    }
+
+    #[test]
+    fn mapping_protocol_getitem_setitem_delitem_contains_len() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (doc, mut map) = doc_map("map");
+            let mut txn = YTransaction(doc.transact());
+
+            assert!(map.__getitem__(&mut txn, "missing").is_err());
+            assert!(!map.__contains__(&mut txn, "missing"));
+            assert_eq!(map.__len__(&txn), 0);
+
+            map.__setitem__(&mut txn, "key", "value".into_py(py)).unwrap();
+            assert!(map.__contains__(&mut txn, "key"));
+            assert_eq!(map.__len__(&txn), 1);
+
+            map.__delitem__(&mut txn, "key").unwrap();
+            assert!(map.__getitem__(&mut txn, "key").is_err());
+        });
+    }
+
+    #[test]
+    fn pop_and_update() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (doc, mut map) = doc_map("map");
+            let mut txn = YTransaction(doc.transact());
+
+            let dict = PyDict::new(py);
+            dict.set_item("a", 1).unwrap();
+            dict.set_item("b", 2).unwrap();
+            map.update(&mut txn, dict).unwrap();
+            assert_eq!(map.length(&txn), 2);
+
+            let popped = map.pop(&mut txn, "a", None).unwrap();
+            assert_eq!(popped.extract::<i64>(py).unwrap(), 1);
+            assert_eq!(map.length(&txn), 1);
+
+            assert!(map.pop(&mut txn, "missing", Some(py.None())).is_ok());
+            assert!(map.pop(&mut txn, "missing", None).is_err());
+        });
+    }
+
+    #[test]
+    fn observe_fires_on_commit_and_unobserve_stops_it() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (doc, mut map) = doc_map("map");
+            let locals = PyDict::new(py);
+            locals.set_item("calls", PyList::empty(py)).unwrap();
+            let callback = py
+                .eval("lambda e: calls.append(e)", Some(locals), None)
+                .unwrap()
+                .to_object(py);
+
+            let mut sub = map.observe(callback).unwrap();
+
+            let mut txn = YTransaction(doc.transact());
+            map.set(&mut txn, "a", py.None()).unwrap();
+            drop(txn);
+
+            let calls: &PyList = locals.get_item("calls").unwrap().downcast().unwrap();
+            assert_eq!(calls.len(), 1);
+
+            sub.unobserve();
+
+            let mut txn = YTransaction(doc.transact());
+            map.set(&mut txn, "b", py.None()).unwrap();
+            drop(txn);
+
+            assert_eq!(calls.len(), 1);
+        });
+    }
+
+    #[test]
+    fn retained_map_event_raises_after_callback_returns() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (doc, mut map) = doc_map("map");
+            let locals = PyDict::new(py);
+            locals.set_item("calls", PyList::empty(py)).unwrap();
+            let callback = py
+                .eval("lambda e: calls.append(e)", Some(locals), None)
+                .unwrap()
+                .to_object(py);
+
+            let _sub = map.observe(callback).unwrap();
+
+            let mut txn = YTransaction(doc.transact());
+            map.set(&mut txn, "a", py.None()).unwrap();
+            drop(txn);
+
+            let calls: &PyList = locals.get_item("calls").unwrap().downcast().unwrap();
+            let retained_event = calls.get_item(0).unwrap();
+
+            assert!(retained_event.call_method0("path").is_err());
+            assert!(retained_event.getattr("keys").is_err());
+        });
+    }
+
+    #[test]
+    fn nested_integrated_map_value_is_wrapped_as_ymap() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let doc = yrs::Doc::new();
+            let inner = doc.get_map("inner");
+            let value = yrs::types::Value::YMap(inner);
+
+            let wrapped = value.into_shared_py(py);
+            assert!(wrapped.extract::<PyRef<YMap>>(py).is_ok());
+        });
+    }
+
+    #[test]
+    fn to_json_recurses_into_preliminary_nested_maps() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let nested_dict = PyDict::new(py);
+            nested_dict.set_item("inner", "value").unwrap();
+            let nested = YMap::new(nested_dict).unwrap();
+
+            let outer_dict = PyDict::new(py);
+            outer_dict
+                .set_item("nested", Py::new(py, nested).unwrap())
+                .unwrap();
+            let outer = YMap::new(outer_dict).unwrap();
+
+            let doc = yrs::Doc::new();
+            let txn = YTransaction(doc.transact());
+            let json = outer.to_json(&txn).unwrap();
+            let json: &PyDict = json.extract(py).unwrap();
+            let nested_json: &PyDict = json.get_item("nested").unwrap().extract().unwrap();
+            assert_eq!(
+                nested_json
+                    .get_item("inner")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "value"
+            );
+        });
+    }
+}